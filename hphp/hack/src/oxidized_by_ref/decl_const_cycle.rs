@@ -0,0 +1,306 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the "hack" directory of this source tree.
+
+//! Detection of cyclic class-constant initialization.
+//!
+//! A constant initializer may reference other constants, e.g.
+//!
+//! ```ignore
+//! class C { const int A = D::A; }
+//! class D { const int A = C::A; }
+//! ```
+//!
+//! The references used during initialization are recorded on
+//! [`ClassConst::refs`] expressly so that cycles like the one above can be
+//! detected. This module builds the directed reference graph out of those
+//! [`ClassConstRef`]s and reports any cycle as a decl error pointing at each
+//! constant's position.
+
+use std::collections::HashMap;
+
+use crate::typing_defs::ClassConst;
+use crate::typing_defs::ClassConstFrom;
+use crate::typing_defs::ClassType;
+
+/// A node in the reference graph: the class a constant originates from paired
+/// with the constant's name.
+pub type ConstId<'a> = (&'a str, &'a str);
+
+/// A cyclic constant initialization. `path` is the sequence of constants that
+/// form the cycle, in traversal order, starting and (implicitly) ending at the
+/// constant where the back-edge was found. A self-reference such as
+/// `const int A = self::A;` yields a length-1 cycle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstCycle<'a> {
+    pub path: Vec<ConstId<'a>>,
+}
+
+/// The graph of constant references, indexed by [`ConstId`]. Each entry keeps
+/// the constant itself (for its position) alongside its outgoing edges.
+struct Graph<'a> {
+    nodes: HashMap<ConstId<'a>, &'a ClassConst<'a>>,
+    edges: HashMap<ConstId<'a>, Vec<ConstId<'a>>>,
+}
+
+impl<'a> Graph<'a> {
+    /// Builds the reference graph from the `consts` of the given classes.
+    /// References to classes or constants not present in `classes` are dropped
+    /// rather than reported — they are diagnosed elsewhere.
+    fn build(classes: &[&'a ClassType<'a>]) -> Self {
+        let mut nodes = HashMap::new();
+        for class in classes {
+            for (name, cc) in class.consts.iter() {
+                nodes.insert((cc.origin, name), *cc);
+            }
+        }
+        let mut edges: HashMap<ConstId<'a>, Vec<ConstId<'a>>> = HashMap::new();
+        for (&id, cc) in nodes.iter() {
+            let (origin, _) = id;
+            let mut outgoing = Vec::new();
+            for r in cc.refs.iter() {
+                let target = match r.0 {
+                    ClassConstFrom::Self_ => (origin, r.1),
+                    ClassConstFrom::From(c) => (c, r.1),
+                };
+                if nodes.contains_key(&target) {
+                    outgoing.push(target);
+                }
+            }
+            edges.insert(id, outgoing);
+        }
+        Graph { nodes, edges }
+    }
+}
+
+/// Three-color marking used by the depth-first cycle search.
+#[derive(Clone, Copy, PartialEq)]
+enum Color {
+    /// Not yet visited.
+    White,
+    /// On the current DFS stack.
+    Gray,
+    /// Fully explored.
+    Black,
+}
+
+/// Returns every cyclic constant-initialization found among `classes`.
+///
+/// Each node is a `(origin_class, const_name)` pair. A depth-first search with
+/// three-color marking is run from every node; reaching a gray node is a
+/// back-edge, and the slice of the gray stack from the revisited node onward is
+/// returned as the cycle path.
+pub fn find_cycles<'a>(classes: &[&'a ClassType<'a>]) -> Vec<ConstCycle<'a>> {
+    let graph = Graph::build(classes);
+    let mut color: HashMap<ConstId<'a>, Color> =
+        graph.nodes.keys().map(|&id| (id, Color::White)).collect();
+    let mut stack: Vec<ConstId<'a>> = Vec::new();
+    let mut cycles: Vec<ConstCycle<'a>> = Vec::new();
+
+    // Iterate the nodes in a stable order so the reported cycles don't depend on
+    // hash-map iteration order across runs.
+    let mut roots: Vec<ConstId<'a>> = graph.nodes.keys().copied().collect();
+    roots.sort_unstable();
+    for root in roots {
+        dfs(&graph, root, &mut color, &mut stack, &mut cycles);
+    }
+    cycles
+}
+
+fn dfs<'a>(
+    graph: &Graph<'a>,
+    node: ConstId<'a>,
+    color: &mut HashMap<ConstId<'a>, Color>,
+    stack: &mut Vec<ConstId<'a>>,
+    cycles: &mut Vec<ConstCycle<'a>>,
+) {
+    match color.get(&node).copied().unwrap_or(Color::White) {
+        Color::Black => return,
+        Color::Gray => {
+            // Back-edge: the cycle is the gray stack from this node onward.
+            if let Some(start) = stack.iter().position(|n| *n == node) {
+                cycles.push(ConstCycle {
+                    path: stack[start..].to_vec(),
+                });
+            }
+            return;
+        }
+        Color::White => {}
+    }
+
+    color.insert(node, Color::Gray);
+    stack.push(node);
+    if let Some(targets) = graph.edges.get(&node) {
+        for &target in targets {
+            dfs(graph, target, color, stack, cycles);
+        }
+    }
+    stack.pop();
+    color.insert(node, Color::Black);
+}
+
+/// Maps a detected cycle back to the position of each constant in it, so a
+/// caller can thread the positions into an [`errors::Errors`] entry.
+pub fn cycle_positions<'a>(
+    classes: &[&'a ClassType<'a>],
+    cycle: &ConstCycle<'a>,
+) -> Vec<&'a pos_or_decl::PosOrDecl<'a>> {
+    let graph = Graph::build(classes);
+    cycle
+        .path
+        .iter()
+        .filter_map(|id| graph.nodes.get(id).map(|cc| cc.pos))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+    use typing_reason::Reason;
+
+    use super::*;
+    use crate::pos_or_decl::PosOrDecl;
+    use crate::s_map::SMap;
+    use crate::s_set::SSet;
+    use crate::typing_defs::ClassConstRef;
+    use crate::typing_defs::ConsistentKind;
+    use crate::typing_defs::Ty;
+    use crate::typing_defs::Ty_;
+
+    /// A constant `origin::name` whose initializer references `refs`.
+    fn cc<'a>(
+        arena: &'a Bump,
+        origin: &'a str,
+        refs: &'a [ClassConstRef<'a>],
+    ) -> &'a ClassConst<'a> {
+        let ty = arena.alloc(Ty(
+            arena.alloc(Reason::none()),
+            arena.alloc(Ty_::Tgeneric(arena.alloc(("T", &[][..])))),
+        ));
+        arena.alloc(ClassConst {
+            synthesized: false,
+            abstract_: false,
+            pos: arena.alloc(PosOrDecl::none()),
+            type_: ty,
+            origin,
+            refs,
+        })
+    }
+
+    /// A class named `name` carrying the given `(const_name, const)` pairs.
+    fn class<'a>(
+        arena: &'a Bump,
+        name: &'a str,
+        consts: &'a [(&'a str, &'a ClassConst<'a>)],
+    ) -> &'a ClassType<'a> {
+        arena.alloc(ClassType {
+            need_init: false,
+            members_fully_known: true,
+            abstract_: false,
+            final_: false,
+            const_: false,
+            deferred_init_members: SSet::empty(),
+            kind: oxidized::ast_defs::ClassKind::Cnormal,
+            is_xhp: false,
+            has_xhp_keyword: false,
+            is_disposable: false,
+            name,
+            pos: arena.alloc(PosOrDecl::none()),
+            tparams: &[],
+            where_constraints: &[],
+            consts: SMap::from(arena, consts.iter().copied()),
+            typeconsts: SMap::empty(),
+            props: SMap::empty(),
+            sprops: SMap::empty(),
+            methods: SMap::empty(),
+            smethods: SMap::empty(),
+            construct: (None, ConsistentKind::Inconsistent),
+            ancestors: SMap::empty(),
+            support_dynamic_type: false,
+            req_ancestors: &[],
+            req_ancestors_extends: SSet::empty(),
+            extends: SSet::empty(),
+            enum_type: None,
+            sealed_whitelist: None,
+            xhp_enum_values: SMap::empty(),
+            decl_errors: None,
+        })
+    }
+
+    #[test]
+    fn detects_two_class_cycle() {
+        // class C { const A = D::A; } class D { const A = C::A; }
+        let arena = &Bump::new();
+        let c = class(
+            arena,
+            "C",
+            arena.alloc([(
+                "A",
+                cc(arena, "C", arena.alloc([ClassConstRef(ClassConstFrom::From("D"), "A")])),
+            )]),
+        );
+        let d = class(
+            arena,
+            "D",
+            arena.alloc([(
+                "A",
+                cc(arena, "D", arena.alloc([ClassConstRef(ClassConstFrom::From("C"), "A")])),
+            )]),
+        );
+        let cycles = find_cycles(&[c, d]);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].path.len(), 2);
+        assert!(cycles[0].path.contains(&("C", "A")));
+        assert!(cycles[0].path.contains(&("D", "A")));
+    }
+
+    #[test]
+    fn detects_self_cycle() {
+        // class C { const int A = self::A; } is a length-1 cycle.
+        let arena = &Bump::new();
+        let c = class(
+            arena,
+            "C",
+            arena.alloc([(
+                "A",
+                cc(arena, "C", arena.alloc([ClassConstRef(ClassConstFrom::Self_, "A")])),
+            )]),
+        );
+        let cycles = find_cycles(&[c]);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].path, vec![("C", "A")]);
+    }
+
+    #[test]
+    fn unknown_references_are_dropped() {
+        // A reference to a class/const not in the set is not an edge, hence no
+        // cycle and no error here.
+        let arena = &Bump::new();
+        let c = class(
+            arena,
+            "C",
+            arena.alloc([(
+                "A",
+                cc(arena, "C", arena.alloc([ClassConstRef(ClassConstFrom::From("Missing"), "A")])),
+            )]),
+        );
+        assert!(find_cycles(&[c]).is_empty());
+    }
+
+    #[test]
+    fn acyclic_chain_has_no_cycle() {
+        // class C { const A = D::A; } class D { const A = 1; }
+        let arena = &Bump::new();
+        let c = class(
+            arena,
+            "C",
+            arena.alloc([(
+                "A",
+                cc(arena, "C", arena.alloc([ClassConstRef(ClassConstFrom::From("D"), "A")])),
+            )]),
+        );
+        let d = class(arena, "D", arena.alloc([("A", cc(arena, "D", &[]))]));
+        assert!(find_cycles(&[c, d]).is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the "hack" directory of this source tree.
+
+//! Stable content-hash of decls for dependency-graph fanout.
+//!
+//! The incremental engine wants a public, cross-run fingerprint of a whole
+//! [`ClassType`]/[`FunElt`]/[`TypedefType`] so it can compare old and new decls
+//! per symbol and only invalidate dependents when the hash differs. This
+//! complements [`EqModuloPos`](eq_modulo_pos::EqModuloPos): where we cache the full
+//! decl we can diff it structurally, and where we cache only a fingerprint we
+//! compare [`DeclHash`]es.
+//!
+//! A [`DeclHash`]:
+//!
+//!   * ignores all `PosOrDecl` fields and the `decl_errors` list, so a pure
+//!     reformat or a change confined to error reporting does not perturb it;
+//!   * is deterministic across processes — it uses [`NoPosHash`] with a fixed
+//!     seed and folds the members of each `SMap`/`SSet` in their (already
+//!     sorted) key order, so the result does not depend on allocation addresses
+//!     or iteration nondeterminism; and
+//!   * forces every `Lazy` field before hashing it.
+
+use no_pos_hash::NoPosHash;
+use no_pos_hash::position_insensitive_hash;
+
+use crate::typing_defs::ClassElt;
+use crate::typing_defs::ClassType;
+use crate::typing_defs::FunElt;
+use crate::typing_defs::TypedefType;
+
+/// A stable, position-insensitive content hash of a decl.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeclHash(pub u64);
+
+/// Deterministic hash of any [`NoPosHash`] value (positions already excluded).
+fn hash_of<T: NoPosHash>(value: &T) -> u64 {
+    position_insensitive_hash(value)
+}
+
+/// Folds a stream of sub-hashes into one in a fixed order. The mix is a
+/// 64-bit variant of the FNV-1a step, chosen because it is associative-free
+/// (order matters, as it must for a canonical fold) and carries no process
+/// state.
+fn fold(seed: u64, parts: impl IntoIterator<Item = u64>) -> u64 {
+    let mut acc = seed;
+    for part in parts {
+        acc ^= part;
+        acc = acc.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    acc
+}
+
+/// Seed for an empty fold. The value is arbitrary but fixed, matching the FNV
+/// offset basis so the fold reduces to plain FNV-1a over its inputs.
+const SEED: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Hashes an inherited member, forcing its `Lazy` type and skipping its
+/// position.
+fn class_elt_hash(elt: &ClassElt<'_>) -> u64 {
+    fold(
+        SEED,
+        [
+            hash_of(&elt.visibility),
+            hash_of(elt.type_.force()),
+            hash_of(&elt.origin),
+            hash_of(&elt.deprecated),
+            hash_of(&elt.flags),
+        ],
+    )
+}
+
+impl<'a> ClassType<'a> {
+    /// Content hash of this class. Changes iff some semantic component of the
+    /// class changes; stable under reformatting and across processes.
+    pub fn decl_hash(&self) -> DeclHash {
+        // Scalar and name content. `decl_errors` and every `pos` are excluded.
+        let mut parts = vec![
+            hash_of(&self.need_init),
+            hash_of(&self.members_fully_known),
+            hash_of(&self.abstract_),
+            hash_of(&self.final_),
+            hash_of(&self.const_),
+            hash_of(&self.deferred_init_members),
+            hash_of(&self.kind),
+            hash_of(&self.is_xhp),
+            hash_of(&self.has_xhp_keyword),
+            hash_of(&self.is_disposable),
+            hash_of(&self.name),
+            hash_of(&self.tparams),
+            hash_of(&self.where_constraints),
+            hash_of(&self.consts),
+            hash_of(&self.typeconsts),
+            hash_of(&self.support_dynamic_type),
+            hash_of(&self.req_ancestors),
+            hash_of(&self.req_ancestors_extends),
+            hash_of(&self.extends),
+            hash_of(&self.enum_type),
+            hash_of(&self.sealed_whitelist),
+            hash_of(&self.xhp_enum_values),
+            hash_of(&self.ancestors),
+        ];
+
+        // Member maps are folded via `combine_members` so the class hash
+        // changes exactly when one member's content does. The maps are sorted
+        // by key, so iteration order is canonical.
+        parts.push(combine_members(self.props.iter().map(|(k, v)| (k, class_elt_hash(v)))).0);
+        parts.push(combine_members(self.sprops.iter().map(|(k, v)| (k, class_elt_hash(v)))).0);
+        parts.push(combine_members(self.methods.iter().map(|(k, v)| (k, class_elt_hash(v)))).0);
+        parts.push(combine_members(self.smethods.iter().map(|(k, v)| (k, class_elt_hash(v)))).0);
+        parts.push(match self.construct.0 {
+            Some(elt) => fold(SEED, [class_elt_hash(elt), hash_of(&self.construct.1)]),
+            None => hash_of(&self.construct.1),
+        });
+
+        DeclHash(fold(SEED, parts))
+    }
+}
+
+impl<'a> FunElt<'a> {
+    /// Content hash of this function decl.
+    pub fn decl_hash(&self) -> DeclHash {
+        DeclHash(fold(
+            SEED,
+            [
+                hash_of(&self.deprecated),
+                hash_of(&self.type_),
+                hash_of(&self.php_std_lib),
+                hash_of(&self.support_dynamic_type),
+            ],
+        ))
+    }
+}
+
+impl<'a> TypedefType<'a> {
+    /// Content hash of this typedef decl.
+    pub fn decl_hash(&self) -> DeclHash {
+        DeclHash(fold(
+            SEED,
+            [
+                hash_of(&self.vis),
+                hash_of(&self.tparams),
+                hash_of(&self.constraint),
+                hash_of(&self.type_),
+            ],
+        ))
+    }
+}
+
+/// Folds a set of named member hashes into a single hash. Each member
+/// contributes both its name and its hash, so renaming or re-hashing a member
+/// changes the result; the caller is responsible for supplying the members in
+/// canonical (sorted-key) order.
+pub fn combine_members<'a>(members: impl IntoIterator<Item = (&'a str, u64)>) -> DeclHash {
+    DeclHash(fold(
+        SEED,
+        members
+            .into_iter()
+            .flat_map(|(name, hash)| [hash_of(&name), hash]),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+
+    use super::*;
+    use crate::pos_or_decl::PosOrDecl;
+    use crate::s_map::SMap;
+    use crate::s_set::SSet;
+    use crate::typing_defs::ConsistentKind;
+
+    /// A bare class named `name`, positioned at `pos`. Only the name differs
+    /// between the classes the tests build; every other field is fixed.
+    fn class<'a>(arena: &'a Bump, name: &'a str, pos: &'a PosOrDecl<'a>) -> &'a ClassType<'a> {
+        arena.alloc(ClassType {
+            need_init: false,
+            members_fully_known: true,
+            abstract_: false,
+            final_: false,
+            const_: false,
+            deferred_init_members: SSet::empty(),
+            kind: oxidized::ast_defs::ClassKind::Cnormal,
+            is_xhp: false,
+            has_xhp_keyword: false,
+            is_disposable: false,
+            name,
+            pos,
+            tparams: &[],
+            where_constraints: &[],
+            consts: SMap::empty(),
+            typeconsts: SMap::empty(),
+            props: SMap::empty(),
+            sprops: SMap::empty(),
+            methods: SMap::empty(),
+            smethods: SMap::empty(),
+            construct: (None, ConsistentKind::Inconsistent),
+            ancestors: SMap::empty(),
+            support_dynamic_type: false,
+            req_ancestors: &[],
+            req_ancestors_extends: SSet::empty(),
+            extends: SSet::empty(),
+            enum_type: None,
+            sealed_whitelist: None,
+            xhp_enum_values: SMap::empty(),
+            decl_errors: None,
+        })
+    }
+
+    #[test]
+    fn hash_is_deterministic_across_allocations() {
+        let arena = &Bump::new();
+        // Same content built twice, in separate allocations (including each
+        // class's `none` position) => same hash, repeatably.
+        let a = class(arena, "C", arena.alloc(PosOrDecl::none()));
+        let b = class(arena, "C", arena.alloc(PosOrDecl::none()));
+        assert_eq!(a.decl_hash(), b.decl_hash());
+        assert_eq!(a.decl_hash(), a.decl_hash());
+    }
+
+    #[test]
+    fn hash_changes_with_semantic_content() {
+        let arena = &Bump::new();
+        let c = class(arena, "C", arena.alloc(PosOrDecl::none()));
+        let d = class(arena, "D", arena.alloc(PosOrDecl::none()));
+        assert_ne!(c.decl_hash(), d.decl_hash());
+    }
+
+    #[test]
+    fn combine_members_depends_on_order_and_names() {
+        let ab = combine_members([("a", 1), ("b", 2)]);
+        let ba = combine_members([("b", 2), ("a", 1)]);
+        // The fold is canonical, so a different key order is a different hash:
+        // callers must supply members in sorted order.
+        assert_ne!(ab, ba);
+        // Renaming a member perturbs the combined hash.
+        assert_ne!(ab, combine_members([("a", 1), ("c", 2)]));
+    }
+}
@@ -0,0 +1,580 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the "hack" directory of this source tree.
+
+//! A compact, versioned binary format for persisting decls.
+//!
+//! Decls otherwise only round-trip through `ocamlrep` (the in-process OCaml
+//! heap) or serde JSON. Neither is suitable for a "remote decl" cache that
+//! ships prebuilt decls to worker processes keyed by file hash: JSON is bulky
+//! and both re-materialize into owned allocations. This module encodes a
+//! [`ClassType`] into a self-describing byte blob that:
+//!
+//!   * begins with a single [`FORMAT_VERSION`] byte, so a consumer built
+//!     against an incompatible layout rejects the blob with
+//!     [`DeserializationError::VersionMismatch`] rather than misreading it;
+//!   * interns the many repeated `&str` fields (class names, member `origin`s)
+//!     into a per-blob string table so each distinct string is stored once; and
+//!   * deserializes directly into a caller-supplied [`Bump`] arena, so decoding
+//!     allocates no intermediate owned values and the blob may be dropped as
+//!     soon as [`deserialize_class_in`] returns.
+//!
+//! Positions and reasons are not semantic content and are not persisted; they
+//! round-trip through their empty base impls below (decoding to
+//! [`pos_or_decl::PosOrDecl::none`] / [`typing_reason::Reason::none`]).
+//! [`EqModuloPos::eq_modulo_pos`](eq_modulo_pos::EqModuloPos::eq_modulo_pos)
+//! ignores positions but still compares reasons;
+//! `eq_modulo_pos_and_reason` ignores both.
+//!
+//! The codec is the pair of derives
+//! [`ToBlob`](decl_store_derive::ToBlob)/[`FromBlob`](decl_store_derive::FromBlob):
+//! every decl type carries them next to its definition (applied by
+//! `oxidized_regen` alongside the existing `EqModuloPos`/`NoPosHash` derives),
+//! and the whole-class entry points below are written in terms of them. The
+//! base impls in this file supply the leaves the derives bottom out at —
+//! primitives, references, slices, tuples, the `SMap`/`SSet` containers, and
+//! `Lazy`.
+
+use std::collections::HashMap;
+
+use bumpalo::Bump;
+pub use decl_store_derive::FromBlob;
+pub use decl_store_derive::ToBlob;
+
+use crate::pos_or_decl::PosOrDecl;
+use crate::s_map::SMap;
+use crate::s_set::SSet;
+use crate::typing_defs::ClassType;
+use crate::typing_defs::DeserializationError;
+
+/// The layout version. Bump this whenever the encoding of any decl component
+/// changes in a way that is not backward compatible.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Accumulates the encoded body and the string table.
+pub struct Writer {
+    body: Vec<u8>,
+    strings: Vec<String>,
+    index: HashMap<String, u32>,
+}
+
+impl Writer {
+    fn new() -> Self {
+        Writer {
+            body: Vec::new(),
+            strings: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    /// Writes a raw byte.
+    pub fn u8(&mut self, b: u8) {
+        self.body.push(b);
+    }
+
+    /// Writes an unsigned integer as a LEB128 varint.
+    pub fn varint(&mut self, mut v: u64) {
+        loop {
+            let byte = (v & 0x7f) as u8;
+            v >>= 7;
+            if v == 0 {
+                self.body.push(byte);
+                break;
+            }
+            self.body.push(byte | 0x80);
+        }
+    }
+
+    /// Writes a signed integer using zigzag encoding.
+    pub fn int(&mut self, v: i64) {
+        self.varint(((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    pub fn bool(&mut self, b: bool) {
+        self.u8(b as u8);
+    }
+
+    /// Writes a string as its id in the per-blob string table.
+    pub fn str(&mut self, s: &str) {
+        if let Some(&id) = self.index.get(s) {
+            self.varint(id as u64);
+            return;
+        }
+        let id = self.strings.len() as u32;
+        self.strings.push(s.to_owned());
+        self.index.insert(s.to_owned(), id);
+        self.varint(id as u64);
+    }
+
+    /// Emits `[version][string table][body]`.
+    fn finish(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.body.len() + 16);
+        out.push(FORMAT_VERSION);
+        // String table: count, then each entry length-prefixed.
+        let mut len = Vec::new();
+        write_varint_to(&mut len, self.strings.len() as u64);
+        out.extend_from_slice(&len);
+        for s in &self.strings {
+            len.clear();
+            write_varint_to(&mut len, s.len() as u64);
+            out.extend_from_slice(&len);
+            out.extend_from_slice(s.as_bytes());
+        }
+        out.extend_from_slice(&self.body);
+        out
+    }
+}
+
+fn write_varint_to(out: &mut Vec<u8>, mut v: u64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Reads from an encoded body, resolving strings against the decoded table and
+/// allocating borrowed data into `arena`.
+pub struct Reader<'a> {
+    pub arena: &'a Bump,
+    strings: &'a [&'a str],
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn err(msg: &'a str) -> DeserializationError<'a> {
+        DeserializationError::DeserializationError(msg)
+    }
+
+    /// Error for an out-of-range enum tag; used by `#[derive(FromBlob)]`.
+    pub fn tag_error(type_name: &'a str) -> DeserializationError<'a> {
+        DeserializationError::DeserializationError(type_name)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DeserializationError<'a>> {
+        let b = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| Self::err("unexpected end of blob"))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    pub fn varint(&mut self) -> Result<u64, DeserializationError<'a>> {
+        let mut result: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            result |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        Ok(result)
+    }
+
+    pub fn int(&mut self) -> Result<i64, DeserializationError<'a>> {
+        let v = self.varint()?;
+        Ok(((v >> 1) as i64) ^ -((v & 1) as i64))
+    }
+
+    pub fn bool(&mut self) -> Result<bool, DeserializationError<'a>> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn str(&mut self) -> Result<&'a str, DeserializationError<'a>> {
+        let id = self.varint()? as usize;
+        self.strings
+            .get(id)
+            .copied()
+            .ok_or_else(|| Self::err("string id out of range"))
+    }
+}
+
+/// Encodes a value into the [`Writer`].
+pub trait ToBlob {
+    fn to_blob(&self, w: &mut Writer);
+}
+
+/// Decodes a value from the [`Reader`], allocating into its arena.
+pub trait FromBlob<'a>: Sized {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>>;
+}
+
+// --- Primitives -----------------------------------------------------------
+
+impl ToBlob for bool {
+    fn to_blob(&self, w: &mut Writer) {
+        w.bool(*self);
+    }
+}
+impl<'a> FromBlob<'a> for bool {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        r.bool()
+    }
+}
+
+impl ToBlob for isize {
+    fn to_blob(&self, w: &mut Writer) {
+        w.int(*self as i64);
+    }
+}
+impl<'a> FromBlob<'a> for isize {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        Ok(r.int()? as isize)
+    }
+}
+
+impl ToBlob for str {
+    fn to_blob(&self, w: &mut Writer) {
+        w.str(self);
+    }
+}
+impl<'a> FromBlob<'a> for &'a str {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        r.str()
+    }
+}
+
+// --- References, options, slices, tuples ----------------------------------
+
+impl<T: ToBlob + ?Sized> ToBlob for &T {
+    fn to_blob(&self, w: &mut Writer) {
+        (**self).to_blob(w)
+    }
+}
+// Decoding a `&'a T` allocates the decoded `T` into the arena. `T` is `Sized`
+// here, so this does not overlap the `&'a str`/`&'a [T]` impls (whose pointees
+// are unsized).
+impl<'a, T: FromBlob<'a> + 'a> FromBlob<'a> for &'a T {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        Ok(r.arena.alloc(T::from_blob(r)?))
+    }
+}
+
+impl<T: ToBlob> ToBlob for Option<T> {
+    fn to_blob(&self, w: &mut Writer) {
+        match self {
+            None => w.bool(false),
+            Some(v) => {
+                w.bool(true);
+                v.to_blob(w);
+            }
+        }
+    }
+}
+impl<'a, T: FromBlob<'a>> FromBlob<'a> for Option<T> {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        if r.bool()? {
+            Ok(Some(T::from_blob(r)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<T: ToBlob> ToBlob for [T] {
+    fn to_blob(&self, w: &mut Writer) {
+        w.varint(self.len() as u64);
+        for v in self {
+            v.to_blob(w);
+        }
+    }
+}
+impl<'a, T: FromBlob<'a> + Copy> FromBlob<'a> for &'a [T] {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        let n = r.varint()? as usize;
+        let mut v = bumpalo::collections::Vec::with_capacity_in(n, r.arena);
+        for _ in 0..n {
+            v.push(T::from_blob(r)?);
+        }
+        Ok(v.into_bump_slice())
+    }
+}
+
+impl<A: ToBlob, B: ToBlob> ToBlob for (A, B) {
+    fn to_blob(&self, w: &mut Writer) {
+        self.0.to_blob(w);
+        self.1.to_blob(w);
+    }
+}
+impl<'a, A: FromBlob<'a>, B: FromBlob<'a>> FromBlob<'a> for (A, B) {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        Ok((A::from_blob(r)?, B::from_blob(r)?))
+    }
+}
+
+// --- Arena collections ----------------------------------------------------
+
+impl<'a, V: ToBlob> ToBlob for SMap<'a, V> {
+    fn to_blob(&self, w: &mut Writer) {
+        w.varint(self.count() as u64);
+        for (k, v) in self.iter() {
+            w.str(k);
+            v.to_blob(w);
+        }
+    }
+}
+impl<'a, V: FromBlob<'a> + Copy> FromBlob<'a> for SMap<'a, V> {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        let n = r.varint()? as usize;
+        let mut pairs = bumpalo::collections::Vec::with_capacity_in(n, r.arena);
+        for _ in 0..n {
+            let k = r.str()?;
+            pairs.push((k, V::from_blob(r)?));
+        }
+        Ok(SMap::from(r.arena, pairs.into_iter()))
+    }
+}
+
+impl<'a> ToBlob for SSet<'a> {
+    fn to_blob(&self, w: &mut Writer) {
+        w.varint(self.count() as u64);
+        for s in self.iter() {
+            w.str(s);
+        }
+    }
+}
+impl<'a> FromBlob<'a> for SSet<'a> {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        let n = r.varint()? as usize;
+        let mut elems = bumpalo::collections::Vec::with_capacity_in(n, r.arena);
+        for _ in 0..n {
+            elems.push(r.str()?);
+        }
+        Ok(SSet::from(r.arena, elems.into_iter()))
+    }
+}
+
+impl<T: ToBlob> ToBlob for lazy::Lazy<T> {
+    fn to_blob(&self, w: &mut Writer) {
+        self.force().to_blob(w);
+    }
+}
+impl<'a, T: FromBlob<'a>> FromBlob<'a> for lazy::Lazy<T> {
+    fn from_blob(r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        Ok(lazy::Lazy::new(T::from_blob(r)?))
+    }
+}
+
+// --- Positions and reasons: no semantic content ---------------------------
+
+// Owned (not `&`) impls, so the `&PosOrDecl`/`&Reason` fields resolve through
+// the blanket `&'a T` impl above and land a fresh copy in the arena. Decoding
+// yields the canonical `none` value, since positions and reasons are never
+// written.
+impl<'a> ToBlob for PosOrDecl<'a> {
+    fn to_blob(&self, _w: &mut Writer) {}
+}
+impl<'a> FromBlob<'a> for PosOrDecl<'a> {
+    fn from_blob(_r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        Ok(*PosOrDecl::none())
+    }
+}
+
+impl<'a> ToBlob for typing_reason::Reason<'a> {
+    fn to_blob(&self, _w: &mut Writer) {}
+}
+impl<'a> FromBlob<'a> for typing_reason::Reason<'a> {
+    fn from_blob(_r: &mut Reader<'a>) -> Result<Self, DeserializationError<'a>> {
+        Ok(*typing_reason::Reason::none())
+    }
+}
+
+/// Serializes a [`ClassType`] to a versioned, string-interned binary blob.
+pub fn serialize_class(class: &ClassType<'_>) -> Vec<u8> {
+    let mut w = Writer::new();
+    class.to_blob(&mut w);
+    w.finish()
+}
+
+/// Deserializes a blob produced by [`serialize_class`] into `arena`.
+///
+/// Returns [`DeserializationError::VersionMismatch`] when the leading version
+/// byte does not match [`FORMAT_VERSION`]. The decoded strings and body are
+/// copied into `arena`, so `blob` need not outlive the returned decl.
+pub fn deserialize_class_in<'a, 'b>(
+    arena: &'a Bump,
+    blob: &'b [u8],
+) -> Result<&'a ClassType<'a>, DeserializationError<'a>> {
+    let version = *blob
+        .first()
+        .ok_or(DeserializationError::DeserializationError("empty blob"))?;
+    if version != FORMAT_VERSION {
+        return Err(DeserializationError::VersionMismatch(
+            "decl_store blob version is not supported by this binary",
+        ));
+    }
+
+    // Decode the string table, copying each entry into the arena so nothing
+    // borrows from `blob` past this call.
+    let mut pos = 1usize;
+    let count = read_varint_at(blob, &mut pos)?;
+    let mut strings = bumpalo::collections::Vec::with_capacity_in(count as usize, arena);
+    for _ in 0..count {
+        let len = read_varint_at(blob, &mut pos)? as usize;
+        let end = pos
+            .checked_add(len)
+            .filter(|&e| e <= blob.len())
+            .ok_or(DeserializationError::DeserializationError(
+                "truncated string table",
+            ))?;
+        let s = std::str::from_utf8(&blob[pos..end])
+            .map_err(|_| DeserializationError::DeserializationError("invalid utf-8 in blob"))?;
+        strings.push(&*arena.alloc_str(s));
+        pos = end;
+    }
+
+    let mut r = Reader {
+        arena,
+        strings: strings.into_bump_slice(),
+        bytes: arena.alloc_slice_copy(&blob[pos..]),
+        pos: 0,
+    };
+    Ok(arena.alloc(ClassType::from_blob(&mut r)?))
+}
+
+fn read_varint_at(bytes: &[u8], pos: &mut usize) -> Result<u64, DeserializationError<'static>> {
+    let mut result: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes
+            .get(*pos)
+            .ok_or(DeserializationError::DeserializationError("truncated varint"))?;
+        *pos += 1;
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use eq_modulo_pos::EqModuloPos;
+
+    use super::*;
+    use crate::typing_defs::ConsistentKind;
+
+    /// A minimal but non-empty class: one const with a reference, exercising the
+    /// `SMap`, tuple, slice, and string-table paths of the codec.
+    fn sample_class<'a>(arena: &'a Bump) -> &'a ClassType<'a> {
+        use crate::typing_defs::ClassConst;
+        use crate::typing_defs::ClassConstFrom;
+        use crate::typing_defs::ClassConstRef;
+        use crate::typing_defs::Ty;
+        use crate::typing_defs::Ty_;
+
+        let ty = arena.alloc(Ty(
+            arena.alloc(typing_reason::Reason::none()),
+            arena.alloc(Ty_::Tgeneric(arena.alloc(("T", &[][..])))),
+        ));
+        let cc = arena.alloc(ClassConst {
+            synthesized: false,
+            abstract_: false,
+            pos: PosOrDecl::none(),
+            type_: ty,
+            origin: "C",
+            refs: arena.alloc([ClassConstRef(ClassConstFrom::Self_, "B")]),
+        });
+        arena.alloc(ClassType {
+            need_init: true,
+            members_fully_known: true,
+            abstract_: false,
+            final_: true,
+            const_: false,
+            deferred_init_members: SSet::empty(),
+            kind: oxidized::ast_defs::ClassKind::Cnormal,
+            is_xhp: false,
+            has_xhp_keyword: false,
+            is_disposable: false,
+            name: "C",
+            pos: PosOrDecl::none(),
+            tparams: &[],
+            where_constraints: &[],
+            consts: SMap::from(arena, [("A", &*cc)].into_iter()),
+            typeconsts: SMap::empty(),
+            props: SMap::empty(),
+            sprops: SMap::empty(),
+            methods: SMap::empty(),
+            smethods: SMap::empty(),
+            construct: (None, ConsistentKind::Inconsistent),
+            ancestors: SMap::empty(),
+            support_dynamic_type: false,
+            req_ancestors: &[],
+            req_ancestors_extends: SSet::empty(),
+            extends: SSet::empty(),
+            enum_type: None,
+            sealed_whitelist: None,
+            xhp_enum_values: SMap::empty(),
+            decl_errors: None,
+        })
+    }
+
+    #[test]
+    fn class_round_trips() {
+        let arena = &Bump::new();
+        let class = sample_class(arena);
+        let blob = serialize_class(class);
+
+        // The blob is dropped before the decoded class is read, proving it need
+        // not outlive the arena.
+        let decoded = {
+            let owned = blob.clone();
+            drop(blob);
+            deserialize_class_in(arena, &owned).unwrap()
+        };
+        // Positions are not persisted, so compare modulo position.
+        assert!(class.eq_modulo_pos(decoded));
+    }
+
+    #[test]
+    fn empty_blob_is_rejected() {
+        let arena = Bump::new();
+        assert!(matches!(
+            deserialize_class_in(&arena, &[]),
+            Err(DeserializationError::DeserializationError(_))
+        ));
+    }
+
+    #[test]
+    fn future_version_is_rejected() {
+        let arena = Bump::new();
+        let blob = [FORMAT_VERSION + 1];
+        assert!(matches!(
+            deserialize_class_in(&arena, &blob),
+            Err(DeserializationError::VersionMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn varint_round_trips() {
+        let mut w = Writer::new();
+        for v in [0u64, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            w.varint(v);
+        }
+        let mut pos = 0;
+        for expected in [0u64, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            assert_eq!(read_varint_at(&w.body, &mut pos).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn string_table_interns_repeats() {
+        let mut w = Writer::new();
+        w.str("Foo");
+        w.str("Foo");
+        w.str("Bar");
+        w.str("Foo");
+        assert_eq!(w.strings, vec!["Foo".to_owned(), "Bar".to_owned()]);
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the "hack" directory of this source tree.
+
+//! [`EqModuloPos`] for the arena-backed string maps and sets used throughout
+//! the decl types. They live here rather than in the `eq_modulo_pos` crate
+//! because that crate sits below `oxidized_by_ref` and cannot name `s_map`/
+//! `s_set`. Both collections keep their entries in sorted key order, so a
+//! pairwise walk is a faithful structural comparison.
+
+use eq_modulo_pos::EqModuloPos;
+
+use crate::s_map::SMap;
+use crate::s_set::SSet;
+
+impl<'a, V: EqModuloPos> EqModuloPos for SMap<'a, V> {
+    fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+        self.iter().count() == rhs.iter().count()
+            && self
+                .iter()
+                .zip(rhs.iter())
+                .all(|((lk, lv), (rk, rv))| lk == rk && lv.eq_modulo_pos(rv))
+    }
+    fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+        self.iter().count() == rhs.iter().count()
+            && self
+                .iter()
+                .zip(rhs.iter())
+                .all(|((lk, lv), (rk, rv))| lk == rk && lv.eq_modulo_pos_and_reason(rv))
+    }
+}
+
+impl<'a> EqModuloPos for SSet<'a> {
+    fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+        self.iter().count() == rhs.iter().count()
+            && self.iter().zip(rhs.iter()).all(|(l, r)| l == r)
+    }
+    fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+        self.eq_modulo_pos(rhs)
+    }
+}
@@ -9,6 +9,9 @@
 //   hphp/hack/src/oxidized_regen.sh
 
 use arena_trait::TrivialDrop;
+use decl_store_derive::FromBlob;
+use decl_store_derive::ToBlob;
+use eq_modulo_pos::EqModuloPos;
 use no_pos_hash::NoPosHash;
 use ocamlrep_derive::FromOcamlRep;
 use ocamlrep_derive::FromOcamlRepIn;
@@ -43,6 +46,8 @@ pub use typing_defs_core::*;
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -50,6 +55,7 @@ pub use typing_defs_core::*;
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub enum ClassConstFrom<'a> {
@@ -78,6 +84,8 @@ arena_deserializer::impl_deserialize_in_arena!(ClassConstFrom<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -85,6 +93,7 @@ arena_deserializer::impl_deserialize_in_arena!(ClassConstFrom<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct ClassConstRef<'a>(
@@ -99,6 +108,8 @@ arena_deserializer::impl_deserialize_in_arena!(ClassConstRef<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -106,6 +117,7 @@ arena_deserializer::impl_deserialize_in_arena!(ClassConstRef<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct ConstDecl<'a> {
@@ -122,6 +134,8 @@ arena_deserializer::impl_deserialize_in_arena!(ConstDecl<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -129,6 +143,7 @@ arena_deserializer::impl_deserialize_in_arena!(ConstDecl<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct ClassElt<'a> {
@@ -154,6 +169,8 @@ arena_deserializer::impl_deserialize_in_arena!(ClassElt<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -161,6 +178,7 @@ arena_deserializer::impl_deserialize_in_arena!(ClassElt<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct FunElt<'a> {
@@ -181,6 +199,8 @@ arena_deserializer::impl_deserialize_in_arena!(FunElt<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -188,6 +208,7 @@ arena_deserializer::impl_deserialize_in_arena!(FunElt<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct ClassConst<'a> {
@@ -213,6 +234,8 @@ arena_deserializer::impl_deserialize_in_arena!(ClassConst<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRep,
     FromOcamlRepIn,
     Hash,
@@ -221,6 +244,7 @@ arena_deserializer::impl_deserialize_in_arena!(ClassConst<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub enum RecordFieldReq {
@@ -235,6 +259,8 @@ arena_deserializer::impl_deserialize_in_arena!(RecordFieldReq);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -242,6 +268,7 @@ arena_deserializer::impl_deserialize_in_arena!(RecordFieldReq);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct RecordDefType<'a> {
@@ -276,6 +303,8 @@ arena_deserializer::impl_deserialize_in_arena!(RecordDefType<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -283,6 +312,7 @@ arena_deserializer::impl_deserialize_in_arena!(RecordDefType<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct Requirement<'a>(
@@ -298,6 +328,8 @@ arena_deserializer::impl_deserialize_in_arena!(Requirement<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -305,6 +337,7 @@ arena_deserializer::impl_deserialize_in_arena!(Requirement<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct ClassType<'a> {
@@ -377,6 +410,8 @@ arena_deserializer::impl_deserialize_in_arena!(ClassType<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -384,6 +419,7 @@ arena_deserializer::impl_deserialize_in_arena!(ClassType<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct AbstractTypeconst<'a> {
@@ -402,6 +438,8 @@ arena_deserializer::impl_deserialize_in_arena!(AbstractTypeconst<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -409,6 +447,7 @@ arena_deserializer::impl_deserialize_in_arena!(AbstractTypeconst<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct ConcreteTypeconst<'a> {
@@ -423,6 +462,8 @@ arena_deserializer::impl_deserialize_in_arena!(ConcreteTypeconst<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -430,6 +471,7 @@ arena_deserializer::impl_deserialize_in_arena!(ConcreteTypeconst<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct PartiallyAbstractTypeconst<'a> {
@@ -447,6 +489,8 @@ arena_deserializer::impl_deserialize_in_arena!(PartiallyAbstractTypeconst<'arena
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -454,6 +498,7 @@ arena_deserializer::impl_deserialize_in_arena!(PartiallyAbstractTypeconst<'arena
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub enum Typeconst<'a> {
@@ -472,6 +517,8 @@ arena_deserializer::impl_deserialize_in_arena!(Typeconst<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -479,6 +526,7 @@ arena_deserializer::impl_deserialize_in_arena!(Typeconst<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct TypeconstType<'a> {
@@ -522,6 +570,8 @@ arena_deserializer::impl_deserialize_in_arena!(TypeconstType<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -529,6 +579,7 @@ arena_deserializer::impl_deserialize_in_arena!(TypeconstType<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct EnumType<'a> {
@@ -548,6 +599,8 @@ arena_deserializer::impl_deserialize_in_arena!(EnumType<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -555,6 +608,7 @@ arena_deserializer::impl_deserialize_in_arena!(EnumType<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub struct TypedefType<'a> {
@@ -577,6 +631,8 @@ arena_deserializer::impl_deserialize_in_arena!(TypedefType<'arena>);
     Debug,
     Deserialize,
     Eq,
+    EqModuloPos,
+    FromBlob,
     FromOcamlRepIn,
     Hash,
     NoPosHash,
@@ -584,6 +640,7 @@ arena_deserializer::impl_deserialize_in_arena!(TypedefType<'arena>);
     PartialEq,
     PartialOrd,
     Serialize,
+    ToBlob,
     ToOcamlRep
 )]
 pub enum DeserializationError<'a> {
@@ -599,6 +656,11 @@ pub enum DeserializationError<'a> {
     /// The input JSON was invalid for some reason.
     #[serde(deserialize_with = "arena_deserializer::arena", borrow)]
     DeserializationError(&'a str),
+    /// The compact binary blob was produced by an incompatible version of the
+    /// [`decl_store`](crate::decl_store) format. The payload is a human-readable
+    /// description of the mismatch.
+    #[serde(deserialize_with = "arena_deserializer::arena", borrow)]
+    VersionMismatch(&'a str),
 }
 impl<'a> TrivialDrop for DeserializationError<'a> {}
 arena_deserializer::impl_deserialize_in_arena!(DeserializationError<'arena>);
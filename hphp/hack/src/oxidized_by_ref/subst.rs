@@ -0,0 +1,393 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the "hack" directory of this source tree.
+
+//! Substitution contexts for specializing inherited members.
+//!
+//! When a member is inherited through a parameterized base class, its type must
+//! be viewed through the concrete type arguments supplied at the `extends`
+//! site. For example, given
+//!
+//! ```ignore
+//! class A<Ta1, Ta2> { public function test(Ta1 $x): Ta2 { ... } }
+//! class B<Tb> extends A<Tb, int> { }
+//! ```
+//!
+//! `A::test` has type `function(Ta1): Ta2`, but seen from `B` it is
+//! `function(Tb): int`. A [`Subst`] maps the base class's type-parameter names
+//! to the concrete types, and [`Subst::apply`] rewrites a [`Ty`] accordingly.
+
+use bumpalo::Bump;
+
+use crate::s_map::SMap;
+use crate::t_shape_map::TShapeMap;
+use crate::typing_defs::ClassElt;
+use crate::typing_defs::FunParam;
+use crate::typing_defs::FunType;
+use crate::typing_defs::PossiblyEnforcedTy;
+use crate::typing_defs::ShapeFieldType;
+use crate::typing_defs::TaccessType;
+use crate::typing_defs::Tparam;
+use crate::typing_defs::Ty;
+use crate::typing_defs::Ty_;
+
+/// A mapping from type-parameter names to the concrete types substituted for
+/// them.
+#[derive(Clone, Debug)]
+pub struct Subst<'a>(pub SMap<'a, &'a Ty<'a>>);
+
+impl<'a> Subst<'a> {
+    /// Derives the substitution that maps each of `tparams` to the
+    /// corresponding type argument in `targs`. Extra parameters (for which no
+    /// argument was supplied) are left unmapped, so references to them pass
+    /// through unchanged.
+    pub fn new(arena: &'a Bump, tparams: &[&'a Tparam<'a>], targs: &[&'a Ty<'a>]) -> Self {
+        let pairs = tparams
+            .iter()
+            .zip(targs.iter())
+            .map(|(tp, ty)| (tp.name.1, *ty));
+        Subst(SMap::from(arena, pairs))
+    }
+
+    /// Rewrites `ty`, replacing every `Tgeneric(name)` whose `name` is in the
+    /// map with its image. Recurses through function parameters and returns,
+    /// tuples, shapes, `Tapply` arguments, and `Taccess` roots. A bare generic
+    /// that is not in the map, and any type the match below does not recurse
+    /// into, is returned as the original allocation; every type that it does
+    /// recurse into is rebuilt, even if no generic inside was substituted.
+    pub fn apply(&self, arena: &'a Bump, ty: &'a Ty<'a>) -> &'a Ty<'a> {
+        let ty_ = match ty.1 {
+            Ty_::Tgeneric(&(name, argl)) => {
+                if argl.is_empty() {
+                    // A bare generic is replaced wholesale by its image.
+                    if let Some(image) = self.0.get(name) {
+                        return image;
+                    }
+                    return ty;
+                }
+                // A higher-kinded generic keeps its head but rewrites its args.
+                let argl = self.apply_list(arena, argl);
+                Ty_::Tgeneric(arena.alloc((name, argl)))
+            }
+            Ty_::Toption(inner) => Ty_::Toption(self.apply(arena, inner)),
+            Ty_::Tlike(inner) => Ty_::Tlike(self.apply(arena, inner)),
+            Ty_::Ttuple(tys) => Ty_::Ttuple(self.apply_list(arena, tys)),
+            Ty_::Tunion(tys) => Ty_::Tunion(self.apply_list(arena, tys)),
+            Ty_::Tintersection(tys) => Ty_::Tintersection(self.apply_list(arena, tys)),
+            Ty_::Tapply(&(name, argl)) => {
+                Ty_::Tapply(arena.alloc((name, self.apply_list(arena, argl))))
+            }
+            Ty_::Tshape(&(shape_kind, fields)) => {
+                // Recurse into each field's type, keeping field names and
+                // optionality unchanged.
+                let fields = TShapeMap::from(
+                    arena,
+                    fields.iter().map(|(name, sft)| {
+                        (
+                            name,
+                            &*arena.alloc(ShapeFieldType {
+                                ty: self.apply(arena, sft.ty),
+                                ..*sft
+                            }),
+                        )
+                    }),
+                );
+                Ty_::Tshape(arena.alloc((shape_kind, fields)))
+            }
+            Ty_::Taccess(access) => {
+                let root = self.apply(arena, access.root_ty);
+                Ty_::Taccess(arena.alloc(TaccessType {
+                    root_ty: root,
+                    ..*access
+                }))
+            }
+            Ty_::Tfun(ft) => {
+                let params = arena.alloc_slice_fill_iter(ft.params.iter().map(|p| {
+                    &*arena.alloc(FunParam {
+                        type_: self.apply_enforced(arena, &p.type_),
+                        ..**p
+                    })
+                }));
+                let ret = self.apply_enforced(arena, &ft.ret);
+                Ty_::Tfun(arena.alloc(FunType {
+                    params,
+                    ret,
+                    ..*ft
+                }))
+            }
+            // The legacy array constructors nest element/key types and so must
+            // recurse as well, or a generic like `darray<_, Ta1>` would pass
+            // through unrewritten.
+            Ty_::Tvarray(inner) => Ty_::Tvarray(self.apply(arena, inner)),
+            Ty_::Tdarray(&(key, value)) => {
+                Ty_::Tdarray(arena.alloc((self.apply(arena, key), self.apply(arena, value))))
+            }
+            Ty_::TvarrayOrDarray(&(key, value)) => {
+                Ty_::TvarrayOrDarray(arena.alloc((self.apply(arena, key), self.apply(arena, value))))
+            }
+            // Leaf types and constructors that cannot mention a generic are
+            // returned as-is. Any new `Ty_` variant that nests a `Ty` (e.g. a
+            // `Tvec_or_dict` in a newer `typing_defs_core`) MUST be handled
+            // above — otherwise a generic buried inside it would survive
+            // unsubstituted, silently producing a wrong specialized type.
+            _ => return ty,
+        };
+        arena.alloc(Ty(ty.0, arena.alloc(ty_)))
+    }
+
+    fn apply_list(&self, arena: &'a Bump, tys: &'a [&'a Ty<'a>]) -> &'a [&'a Ty<'a>] {
+        arena.alloc_slice_fill_iter(tys.iter().map(|ty| self.apply(arena, ty)))
+    }
+
+    fn apply_enforced(
+        &self,
+        arena: &'a Bump,
+        ty: &'a PossiblyEnforcedTy<'a>,
+    ) -> &'a PossiblyEnforcedTy<'a> {
+        arena.alloc(PossiblyEnforcedTy {
+            type_: self.apply(arena, ty.type_),
+            ..*ty
+        })
+    }
+}
+
+/// Specializes an inherited [`ClassElt`]'s type as seen from the subclass.
+///
+/// `parent_tparams` are the base class's type parameters and `ancestor` is the
+/// applied parent type recorded in [`ClassType::ancestors`] (e.g. `A<Tb, int>`
+/// in the module-level example). The substitution derived from matching the
+/// ancestor's type arguments against `parent_tparams` is applied to the elt's
+/// (forced) type, yielding the member type in the inheriting class's context.
+pub fn specialize_elt<'a>(
+    arena: &'a Bump,
+    parent_tparams: &[&'a Tparam<'a>],
+    ancestor: &'a Ty<'a>,
+    elt: &'a ClassElt<'a>,
+) -> &'a Ty<'a> {
+    let targs = match ancestor.1 {
+        Ty_::Tapply(&(_, argl)) => argl,
+        _ => &[],
+    };
+    let subst = Subst::new(arena, parent_tparams, targs);
+    // `type_` is a `Lazy<&Ty>`, so `force` yields `&&Ty`; deref once to hand
+    // `apply` the `&Ty` it expects.
+    subst.apply(arena, *elt.type_.force())
+}
+
+#[cfg(test)]
+mod tests {
+    use eq_modulo_pos::EqModuloPos;
+    use pos::Pos;
+    use typing_reason::Reason;
+
+    use super::*;
+    use crate::pos_or_decl;
+    use crate::t_shape_map;
+    use crate::typing_defs::Capability;
+    use crate::typing_defs::CeVisibility;
+    use crate::typing_defs::Enforcement;
+    use crate::typing_defs::FunImplicitParams;
+    use crate::typing_defs::IfcFunDecl;
+    use crate::typing_defs::ShapeKind;
+
+    /// Allocates a `Ty` with a throwaway reason, mirroring how `apply` rebuilds
+    /// nodes.
+    fn ty<'a>(arena: &'a Bump, ty_: Ty_<'a>) -> &'a Ty<'a> {
+        arena.alloc(Ty(arena.alloc(Reason::none()), arena.alloc(ty_)))
+    }
+
+    /// A bare (non-higher-kinded) generic `name`.
+    fn tgeneric<'a>(arena: &'a Bump, name: &'a str) -> &'a Ty<'a> {
+        ty(arena, Ty_::Tgeneric(arena.alloc((name, &[][..]))))
+    }
+
+    /// A `Tapply` of `name` to `args`, standing in for a concrete class type.
+    fn tapply<'a>(arena: &'a Bump, name: &'a str, args: &'a [&'a Ty<'a>]) -> &'a Ty<'a> {
+        let id = arena.alloc((&*arena.alloc(Pos::none()), name));
+        ty(arena, Ty_::Tapply(arena.alloc((*id, args))))
+    }
+
+    /// A base-class type parameter named `name` with no bounds. Only `name` is
+    /// read by [`Subst::new`].
+    fn tparam<'a>(arena: &'a Bump, name: &'a str) -> &'a Tparam<'a> {
+        arena.alloc(Tparam {
+            variance: oxidized::ast_defs::Variance::Invariant,
+            name: (&*arena.alloc(Pos::none()), name),
+            tparams: &[],
+            constraints: &[],
+            reified: oxidized::ast_defs::ReifyKind::Erased,
+            user_attributes: &[],
+        })
+    }
+
+    /// The substitution `[Ta1 => Tb, Ta2 => int]` used throughout these tests,
+    /// modelling `class B<Tb> extends A<Tb, int>`.
+    fn b_extends_a<'a>(arena: &'a Bump) -> Subst<'a> {
+        let tb = tgeneric(arena, "Tb");
+        let int = tapply(arena, "\\int", &[]);
+        Subst(SMap::from(
+            arena,
+            [("Ta1", tb), ("Ta2", int)].into_iter(),
+        ))
+    }
+
+    #[test]
+    fn substitutes_bare_generic_and_passes_through_unmapped() {
+        let arena = &Bump::new();
+        let subst = b_extends_a(arena);
+        // Ta1 maps to Tb.
+        assert_eq!(subst.apply(arena, tgeneric(arena, "Ta1")).1, tgeneric(arena, "Tb").1);
+        // An unmapped generic is returned unchanged (same allocation).
+        let tc = tgeneric(arena, "Tc");
+        assert!(std::ptr::eq(subst.apply(arena, tc), tc));
+    }
+
+    #[test]
+    fn recurses_through_tuple_and_tapply() {
+        let arena = &Bump::new();
+        let subst = b_extends_a(arena);
+        let tup = ty(
+            arena,
+            Ty_::Ttuple(arena.alloc_slice_fill_iter(
+                [tgeneric(arena, "Ta1"), tgeneric(arena, "Ta2")].into_iter(),
+            )),
+        );
+        let expected = ty(
+            arena,
+            Ty_::Ttuple(arena.alloc_slice_fill_iter(
+                [tgeneric(arena, "Tb"), tapply(arena, "\\int", &[])].into_iter(),
+            )),
+        );
+        assert!(subst.apply(arena, tup).eq_modulo_pos(expected));
+
+        let vec_ta1 = tapply(arena, "\\Vector", arena.alloc_slice_fill_iter([tgeneric(arena, "Ta1")].into_iter()));
+        let vec_tb = tapply(arena, "\\Vector", arena.alloc_slice_fill_iter([tgeneric(arena, "Tb")].into_iter()));
+        assert!(subst.apply(arena, vec_ta1).eq_modulo_pos(vec_tb));
+    }
+
+    #[test]
+    fn recurses_through_shape_field_types() {
+        let arena = &Bump::new();
+        let subst = b_extends_a(arena);
+        let field_name = arena.alloc(t_shape_map::TShapeField::TSFlitStr(arena.alloc((
+            &*arena.alloc(pos_or_decl::PosOrDecl::none()),
+            "k",
+        ))));
+        let sft = arena.alloc(ShapeFieldType {
+            optional: false,
+            ty: tgeneric(arena, "Ta1"),
+        });
+        let fields = TShapeMap::from(arena, [(&*field_name, &*sft)].into_iter());
+        let shape = ty(arena, Ty_::Tshape(arena.alloc((ShapeKind::ClosedShape, fields))));
+        match subst.apply(arena, shape).1 {
+            Ty_::Tshape(&(_, fields)) => {
+                let (_, sft) = fields.iter().next().unwrap();
+                assert!(sft.ty.eq_modulo_pos(tgeneric(arena, "Tb")));
+            }
+            _ => panic!("expected a shape"),
+        }
+    }
+
+    #[test]
+    fn recurses_into_taccess_root() {
+        let arena = &Bump::new();
+        let subst = b_extends_a(arena);
+        let id = arena.alloc((&*arena.alloc(pos_or_decl::PosOrDecl::none()), "T"));
+        let access = ty(
+            arena,
+            Ty_::Taccess(arena.alloc(TaccessType {
+                root_ty: tgeneric(arena, "Ta1"),
+                id: *id,
+            })),
+        );
+        match subst.apply(arena, access).1 {
+            Ty_::Taccess(access) => assert!(access.root_ty.eq_modulo_pos(tgeneric(arena, "Tb"))),
+            _ => panic!("expected a Taccess"),
+        }
+    }
+
+    #[test]
+    fn rewrites_higher_kinded_generic_args() {
+        let arena = &Bump::new();
+        let subst = b_extends_a(arena);
+        // `Ta1<Ta2>` rewrites its argument but keeps the head name.
+        let hk = ty(
+            arena,
+            Ty_::Tgeneric(arena.alloc((
+                "Ta1",
+                arena.alloc_slice_fill_iter([tgeneric(arena, "Ta2")].into_iter()) as &[_],
+            ))),
+        );
+        match subst.apply(arena, hk).1 {
+            Ty_::Tgeneric(&(name, argl)) => {
+                assert_eq!(name, "Ta1");
+                assert!(argl[0].eq_modulo_pos(tapply(arena, "\\int", &[])));
+            }
+            _ => panic!("expected a Tgeneric"),
+        }
+    }
+
+    #[test]
+    fn substitutes_function_param_and_return() {
+        let arena = &Bump::new();
+        let subst = b_extends_a(arena);
+        // `function(Ta1): Ta2` seen from `B<Tb> extends A<Tb, int>` becomes
+        // `function(Tb): int`.
+        let param = arena.alloc(FunParam {
+            pos: arena.alloc(pos_or_decl::PosOrDecl::none()),
+            name: None,
+            type_: PossiblyEnforcedTy {
+                enforced: Enforcement::Unenforced,
+                type_: tgeneric(arena, "Ta1"),
+            },
+            flags: 0,
+        });
+        let ft = arena.alloc(FunType {
+            tparams: &[],
+            where_constraints: &[],
+            params: arena.alloc_slice_fill_iter([&*param].into_iter()),
+            implicit_params: arena.alloc(FunImplicitParams {
+                capability: Capability::CapDefaults(arena.alloc(pos_or_decl::PosOrDecl::none())),
+            }),
+            ret: PossiblyEnforcedTy {
+                enforced: Enforcement::Unenforced,
+                type_: tgeneric(arena, "Ta2"),
+            },
+            flags: 0,
+            ifc_decl: IfcFunDecl::FDPolicied(Some("PUBLIC")),
+        });
+        match subst.apply(arena, ty(arena, Ty_::Tfun(ft))).1 {
+            Ty_::Tfun(ft) => {
+                assert!(ft.params[0].type_.type_.eq_modulo_pos(tgeneric(arena, "Tb")));
+                assert!(ft.ret.type_.eq_modulo_pos(tapply(arena, "\\int", &[])));
+            }
+            _ => panic!("expected a Tfun"),
+        }
+    }
+
+    #[test]
+    fn specializes_inherited_elt_type() {
+        let arena = &Bump::new();
+        // `class A<Ta1, Ta2>` declares a member of type `Ta2`; seen from
+        // `class B<Tb> extends A<Tb, int>` it specializes to `int`.
+        let parent_tparams = &[tparam(arena, "Ta1"), tparam(arena, "Ta2")][..];
+        let ancestor = tapply(
+            arena,
+            "\\A",
+            arena.alloc_slice_fill_iter(
+                [tgeneric(arena, "Tb"), tapply(arena, "\\int", &[])].into_iter(),
+            ),
+        );
+        let elt = arena.alloc(ClassElt {
+            visibility: CeVisibility::Vpublic,
+            type_: arena.alloc(lazy::Lazy::new(tgeneric(arena, "Ta2"))),
+            origin: "\\A",
+            deprecated: None,
+            pos: arena.alloc(lazy::Lazy::new(&*arena.alloc(pos_or_decl::PosOrDecl::none()))),
+            flags: 0,
+        });
+        let specialized = specialize_elt(arena, parent_tparams, ancestor, elt);
+        assert!(specialized.eq_modulo_pos(tapply(arena, "\\int", &[])));
+    }
+}
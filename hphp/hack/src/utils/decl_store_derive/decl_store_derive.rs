@@ -0,0 +1,177 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the "hack" directory of this source tree.
+
+//! Derives for the compact decl codec in
+//! [`oxidized_by_ref::decl_store`](../oxidized_by_ref/decl_store.rs).
+//!
+//! `#[derive(ToBlob)]` walks each field in declaration order and encodes it
+//! through the [`ToBlob`] impl for its type; `#[derive(FromBlob)]` reads the
+//! fields back in the same order. Enums are tagged with a single leading byte
+//! giving the variant index. Positions and reasons carry no semantic content
+//! and round-trip through their own (empty) base impls, so the derives need no
+//! per-field annotations.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates an [`oxidized_by_ref::decl_store::ToBlob`] impl that encodes every
+/// field in order.
+#[proc_macro_derive(ToBlob)]
+pub fn derive_to_blob(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        syn::Data::Struct(s) => {
+            let writes = s.fields.iter().enumerate().map(|(i, f)| {
+                let accessor = field_accessor(&f.ident, i);
+                quote!(::oxidized_by_ref::decl_store::ToBlob::to_blob(&self.#accessor, w);)
+            });
+            quote!(#(#writes)*)
+        }
+        syn::Data::Enum(e) => {
+            let arms = e.variants.iter().enumerate().map(|(tag, v)| {
+                let vname = &v.ident;
+                let tag = tag as u8;
+                let binds: Vec<_> = (0..v.fields.len())
+                    .map(|i| quote::format_ident!("f{}", i))
+                    .collect();
+                let writes = binds
+                    .iter()
+                    .map(|b| quote!(::oxidized_by_ref::decl_store::ToBlob::to_blob(#b, w);));
+                match &v.fields {
+                    syn::Fields::Unit => quote!(#name::#vname => { w.u8(#tag); }),
+                    syn::Fields::Unnamed(_) => {
+                        quote!(#name::#vname(#(#binds),*) => { w.u8(#tag); #(#writes)* })
+                    }
+                    syn::Fields::Named(fields) => {
+                        let names = fields.named.iter().map(|f| f.ident.clone().unwrap());
+                        quote!(#name::#vname { #(#names: #binds),* } => { w.u8(#tag); #(#writes)* })
+                    }
+                }
+            });
+            quote!(match self { #(#arms)* })
+        }
+        syn::Data::Union(_) => {
+            return syn::Error::new_spanned(name, "ToBlob cannot be derived for unions")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    quote! {
+        impl #impl_generics ::oxidized_by_ref::decl_store::ToBlob for #name #ty_generics #where_clause {
+            fn to_blob(&self, w: &mut ::oxidized_by_ref::decl_store::Writer) {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+/// Generates an [`oxidized_by_ref::decl_store::FromBlob`] impl that decodes the
+/// fields in the same order `ToBlob` wrote them, allocating borrowed data into
+/// the reader's arena.
+#[proc_macro_derive(FromBlob)]
+pub fn derive_from_blob(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    let name = &input.ident;
+    let lt = decode_lifetime(&input.generics);
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+    let impl_generics = impl_generics_with(&input.generics, &lt);
+
+    let read = quote!(::oxidized_by_ref::decl_store::FromBlob::from_blob(r)?);
+    let body = match &input.data {
+        syn::Data::Struct(s) => {
+            let ctor = build_ctor(&quote!(#name), &s.fields, &read);
+            quote!(Ok(#ctor))
+        }
+        syn::Data::Enum(e) => {
+            let arms = e.variants.iter().enumerate().map(|(tag, v)| {
+                let tag = tag as u8;
+                let vname = &v.ident;
+                let ctor = build_ctor(&quote!(#name::#vname), &v.fields, &read);
+                quote!(#tag => Ok(#ctor),)
+            });
+            quote! {
+                match r.u8()? {
+                    #(#arms)*
+                    _ => Err(::oxidized_by_ref::decl_store::Reader::tag_error(stringify!(#name))),
+                }
+            }
+        }
+        syn::Data::Union(_) => {
+            return syn::Error::new_spanned(name, "FromBlob cannot be derived for unions")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    quote! {
+        impl #impl_generics ::oxidized_by_ref::decl_store::FromBlob<#lt> for #name #ty_generics #where_clause {
+            fn from_blob(
+                r: &mut ::oxidized_by_ref::decl_store::Reader<#lt>,
+            ) -> ::std::result::Result<Self, ::oxidized_by_ref::typing_defs::DeserializationError<#lt>> {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+/// Builds `Ctor { f: <read>, .. }` / `Ctor(<read>, ..)` / `Ctor` for the given
+/// fields. The caller wraps the result in `Ok(..)`: the struct case in
+/// `derive_from_blob`, and each enum arm on its own.
+fn build_ctor(path: &TokenStream, fields: &syn::Fields, read: &TokenStream) -> TokenStream {
+    match fields {
+        syn::Fields::Unit => quote!(#path),
+        syn::Fields::Unnamed(f) => {
+            let reads = f.unnamed.iter().map(|_| read);
+            quote!(#path(#(#reads),*))
+        }
+        syn::Fields::Named(f) => {
+            let inits = f.named.iter().map(|f| {
+                let id = f.ident.as_ref().unwrap();
+                quote!(#id: #read)
+            });
+            quote!(#path { #(#inits),* })
+        }
+    }
+}
+
+fn field_accessor(ident: &Option<syn::Ident>, i: usize) -> TokenStream {
+    match ident {
+        Some(id) => quote!(#id),
+        None => {
+            let idx = syn::Index::from(i);
+            quote!(#idx)
+        }
+    }
+}
+
+/// The lifetime the decoded value borrows for. Decl types carry a single
+/// lifetime (`'a`); position-free types such as `RecordFieldReq` carry none, so
+/// a fresh `'de` is introduced.
+fn decode_lifetime(generics: &syn::Generics) -> TokenStream {
+    match generics.lifetimes().next() {
+        Some(lt) => {
+            let lt = &lt.lifetime;
+            quote!(#lt)
+        }
+        None => quote!('de),
+    }
+}
+
+/// The impl generics, ensuring the decode lifetime is bound even when the type
+/// itself is not lifetime-parameterized.
+fn impl_generics_with(generics: &syn::Generics, lt: &TokenStream) -> TokenStream {
+    if generics.lifetimes().next().is_some() {
+        let (impl_generics, _, _) = generics.split_for_impl();
+        quote!(#impl_generics)
+    } else {
+        quote!(<#lt>)
+    }
+}
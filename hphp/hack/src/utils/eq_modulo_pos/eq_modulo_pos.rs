@@ -0,0 +1,159 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the "hack" directory of this source tree.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+pub use eq_modulo_pos_derive::EqModuloPos;
+
+/// Structural equality which ignores positions.
+///
+/// Two values compare equal under [`eq_modulo_pos`] when they are equal after
+/// replacing every `Pos`/`PosOrDecl` they contain with a single canonical
+/// position. This lets the incremental engine decide whether the *semantic*
+/// content of a decl actually changed after a file edit, rather than merely
+/// shifting around in the source (line insertions, reformatting, and the like),
+/// so that we can prune the fanout of dependent rechecks.
+///
+/// The derive walks every field structurally. Fields whose type is a position
+/// (`&PosOrDecl`, `Pos`, or a `Lazy` wrapper around one) are treated as always
+/// equal, and any `Lazy`-wrapped field is forced before being compared.
+pub trait EqModuloPos {
+    fn eq_modulo_pos(&self, rhs: &Self) -> bool;
+
+    /// Like [`eq_modulo_pos`], but additionally treats the `Reason` carried by
+    /// a locl `Ty` as equal. Comparing locl types this way lets us tell whether
+    /// a type changed in a way that matters to downstream typing, ignoring the
+    /// reason trail that records *why* the type was inferred.
+    fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool;
+}
+
+/// Leaf types whose equality already ignores positions (they contain none).
+macro_rules! impl_with_equality {
+    ($($ty:ty,)*) => {$(
+        impl EqModuloPos for $ty {
+            #[inline]
+            fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+                self == rhs
+            }
+            #[inline]
+            fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+                self == rhs
+            }
+        }
+    )*};
+}
+
+impl_with_equality! {
+    (), bool, char, isize, usize,
+    i8, i16, i32, i64, i128,
+    u8, u16, u32, u64, u128,
+    str, String,
+}
+
+impl<T: EqModuloPos + ?Sized> EqModuloPos for &T {
+    fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+        (**self).eq_modulo_pos(&**rhs)
+    }
+    fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+        (**self).eq_modulo_pos_and_reason(&**rhs)
+    }
+}
+
+impl<T: EqModuloPos + ?Sized> EqModuloPos for Box<T> {
+    fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+        (**self).eq_modulo_pos(&**rhs)
+    }
+    fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+        (**self).eq_modulo_pos_and_reason(&**rhs)
+    }
+}
+
+impl<T: EqModuloPos> EqModuloPos for Option<T> {
+    fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (None, None) => true,
+            (Some(lhs), Some(rhs)) => lhs.eq_modulo_pos(rhs),
+            _ => false,
+        }
+    }
+    fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (None, None) => true,
+            (Some(lhs), Some(rhs)) => lhs.eq_modulo_pos_and_reason(rhs),
+            _ => false,
+        }
+    }
+}
+
+impl<T: EqModuloPos> EqModuloPos for [T] {
+    fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+        self.len() == rhs.len()
+            && self.iter().zip(rhs.iter()).all(|(l, r)| l.eq_modulo_pos(r))
+    }
+    fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+        self.len() == rhs.len()
+            && self
+                .iter()
+                .zip(rhs.iter())
+                .all(|(l, r)| l.eq_modulo_pos_and_reason(r))
+    }
+}
+
+impl<T: EqModuloPos> EqModuloPos for Vec<T> {
+    fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+        self.as_slice().eq_modulo_pos(rhs.as_slice())
+    }
+    fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+        self.as_slice().eq_modulo_pos_and_reason(rhs.as_slice())
+    }
+}
+
+impl<K: Ord + EqModuloPos, V: EqModuloPos> EqModuloPos for BTreeMap<K, V> {
+    fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+        self.len() == rhs.len()
+            && self
+                .iter()
+                .zip(rhs.iter())
+                .all(|((lk, lv), (rk, rv))| lk.eq_modulo_pos(rk) && lv.eq_modulo_pos(rv))
+    }
+    fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+        self.len() == rhs.len()
+            && self.iter().zip(rhs.iter()).all(|((lk, lv), (rk, rv))| {
+                lk.eq_modulo_pos_and_reason(rk) && lv.eq_modulo_pos_and_reason(rv)
+            })
+    }
+}
+
+impl<T: Ord + EqModuloPos> EqModuloPos for BTreeSet<T> {
+    fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+        self.len() == rhs.len()
+            && self.iter().zip(rhs.iter()).all(|(l, r)| l.eq_modulo_pos(r))
+    }
+    fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+        self.len() == rhs.len()
+            && self
+                .iter()
+                .zip(rhs.iter())
+                .all(|(l, r)| l.eq_modulo_pos_and_reason(r))
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($idx:tt : $ty:ident),+) => {
+        impl<$($ty: EqModuloPos),+> EqModuloPos for ($($ty,)+) {
+            fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+                $(self.$idx.eq_modulo_pos(&rhs.$idx))&&+
+            }
+            fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+                $(self.$idx.eq_modulo_pos_and_reason(&rhs.$idx))&&+
+            }
+        }
+    };
+}
+
+impl_tuple!(0: A, 1: B);
+impl_tuple!(0: A, 1: B, 2: C);
+impl_tuple!(0: A, 1: B, 2: C, 3: D);
@@ -0,0 +1,196 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the "hack" directory of this source tree.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::GenericArgument;
+use syn::PathArguments;
+use syn::Type;
+
+/// `#[derive(EqModuloPos)]` generates an [`EqModuloPos`] impl whose two methods
+/// diverge in what they ignore:
+///
+///   * `eq_modulo_pos` compares every field structurally, treating only
+///     positions (`Pos`, `PosOrDecl`, or a `Lazy` wrapping one) as always
+///     equal;
+///   * `eq_modulo_pos_and_reason` additionally treats any `typing_reason::Reason`
+///     field (notably `Ty.0`) as always equal, so two `Ty`s that differ only in
+///     their reason trail compare equal.
+///
+/// `Lazy`-wrapped fields whose payload is not a position are forced before
+/// being compared.
+#[proc_macro_derive(EqModuloPos)]
+pub fn derive_eq_modulo_pos(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let pos_body = match build_body(name, &input.data, &quote!(eq_modulo_pos), false) {
+        Ok(body) => body,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let reason_body = match build_body(name, &input.data, &quote!(eq_modulo_pos_and_reason), true) {
+        Ok(body) => body,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let output = quote! {
+        impl #impl_generics ::eq_modulo_pos::EqModuloPos for #name #ty_generics #where_clause {
+            fn eq_modulo_pos(&self, rhs: &Self) -> bool {
+                #pos_body
+            }
+            fn eq_modulo_pos_and_reason(&self, rhs: &Self) -> bool {
+                #reason_body
+            }
+        }
+    };
+    output.into()
+}
+
+/// Returns the last path segment's identifier, peeling references.
+fn last_ident(ty: &Type) -> Option<&syn::Ident> {
+    match ty {
+        Type::Reference(r) => last_ident(&r.elem),
+        Type::Path(p) => p.path.segments.last().map(|s| &s.ident),
+        _ => None,
+    }
+}
+
+/// The type argument of a single-parameter generic such as `Lazy<T>`, peeling
+/// references first.
+fn sole_type_arg(ty: &Type) -> Option<&Type> {
+    let seg = match ty {
+        Type::Reference(r) => return sole_type_arg(&r.elem),
+        Type::Path(p) => p.path.segments.last()?,
+        _ => return None,
+    };
+    match &seg.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|a| match a {
+            GenericArgument::Type(t) => Some(t),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// A field that carries no semantic content: a position, or a `Lazy` wrapping a
+/// position.
+fn is_pos(ty: &Type) -> bool {
+    match last_ident(ty) {
+        Some(id) if id == "Pos" || id == "PosOrDecl" => true,
+        Some(id) if id == "Lazy" => sole_type_arg(ty).map_or(false, is_pos),
+        _ => false,
+    }
+}
+
+fn is_lazy(ty: &Type) -> bool {
+    matches!(last_ident(ty), Some(id) if id == "Lazy")
+}
+
+fn is_reason(ty: &Type) -> bool {
+    matches!(last_ident(ty), Some(id) if id == "Reason")
+}
+
+fn build_body(
+    name: &syn::Ident,
+    data: &syn::Data,
+    method: &TokenStream,
+    skip_reason: bool,
+) -> syn::Result<TokenStream> {
+    Ok(match data {
+        syn::Data::Struct(s) => {
+            let cmps = s.fields.iter().enumerate().map(|(i, f)| {
+                let accessor = match &f.ident {
+                    Some(id) => quote!(#id),
+                    None => {
+                        let idx = syn::Index::from(i);
+                        quote!(#idx)
+                    }
+                };
+                compare_field(
+                    &f.ty,
+                    method,
+                    skip_reason,
+                    &quote!(self.#accessor),
+                    &quote!(rhs.#accessor),
+                )
+            });
+            quote!(true #(&& #cmps)*)
+        }
+        syn::Data::Enum(e) => {
+            let arms = e
+                .variants
+                .iter()
+                .map(|v| compare_variant(name, v, method, skip_reason));
+            quote! {
+                match (self, rhs) {
+                    #(#arms)*
+                    _ => false,
+                }
+            }
+        }
+        syn::Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "EqModuloPos cannot be derived for unions",
+            ));
+        }
+    })
+}
+
+/// Emits the comparison expression for a single field, honouring the position,
+/// `Lazy`, and reason special cases.
+fn compare_field(
+    ty: &Type,
+    method: &TokenStream,
+    skip_reason: bool,
+    lhs: &TokenStream,
+    rhs: &TokenStream,
+) -> TokenStream {
+    if is_pos(ty) {
+        quote!(true)
+    } else if skip_reason && is_reason(ty) {
+        quote!(true)
+    } else if is_lazy(ty) {
+        quote!(::eq_modulo_pos::EqModuloPos::#method(#lhs.force(), #rhs.force()))
+    } else {
+        quote!(::eq_modulo_pos::EqModuloPos::#method(&#lhs, &#rhs))
+    }
+}
+
+fn compare_variant(
+    name: &syn::Ident,
+    v: &syn::Variant,
+    method: &TokenStream,
+    skip_reason: bool,
+) -> TokenStream {
+    let vname = &v.ident;
+    let lhs_binds: Vec<_> = (0..v.fields.len())
+        .map(|i| quote::format_ident!("l{}", i))
+        .collect();
+    let rhs_binds: Vec<_> = (0..v.fields.len())
+        .map(|i| quote::format_ident!("r{}", i))
+        .collect();
+
+    let pat = match &v.fields {
+        syn::Fields::Unit => quote!((#name::#vname, #name::#vname)),
+        syn::Fields::Unnamed(_) => {
+            quote!((#name::#vname(#(#lhs_binds),*), #name::#vname(#(#rhs_binds),*)))
+        }
+        syn::Fields::Named(fields) => {
+            let names: Vec<_> = fields.named.iter().map(|f| f.ident.clone().unwrap()).collect();
+            let lpat = names.iter().zip(&lhs_binds).map(|(n, b)| quote!(#n: #b));
+            let rpat = names.iter().zip(&rhs_binds).map(|(n, b)| quote!(#n: #b));
+            quote!((#name::#vname { #(#lpat),* }, #name::#vname { #(#rpat),* }))
+        }
+    };
+
+    let cmps = v
+        .fields
+        .iter()
+        .zip(lhs_binds.iter().zip(rhs_binds.iter()))
+        .map(|(f, (l, r))| compare_field(&f.ty, method, skip_reason, &quote!((*#l)), &quote!((*#r))));
+    quote!(#pat => true #(&& #cmps)*,)
+}